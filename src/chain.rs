@@ -250,6 +250,186 @@ impl<S, J, R> CtzChain<S, J, R> {
     }
 }
 
+/// Computes the binomial coefficient `C(n, k)`.
+fn binom(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    let mut result = 1;
+    for i in 0 .. k {
+        result = result * (n - i) / (i + 1);
+    }
+    result
+}
+
+/// The maximum number of steps that can be reversed exactly using `c`
+/// checkpoint slots and `r` allowed forward recomputations
+/// (Griewank-Walther): `beta(c, r) = C(c + r, c)`.
+fn beta(c: usize, r: usize) -> usize {
+    binom(c + r, c)
+}
+
+/// The smallest `r` such that `beta(c, r) >= len`.
+fn min_recomputations(c: usize, len: usize) -> usize {
+    let mut r = 0;
+    while beta(c, r) < len {
+        r += 1;
+    }
+    r
+}
+
+/// The greedy binomial split: how far to advance from `capo` before taking
+/// the next checkpoint, so that the forward sub-range (up to the new
+/// checkpoint) fits within `beta(c - 1, r)` steps and the remaining tail
+/// fits within `beta(c, r - 1)` steps.
+fn advance(c: usize, r: usize, l: usize) -> usize {
+    if l <= 1 {
+        return 0;
+    }
+    let max_front = beta(c.saturating_sub(1), r);
+    let min_front = l.saturating_sub(beta(c, r.saturating_sub(1)));
+    max_front.min(l - 1).max(min_front)
+}
+
+/// Reverses a range of length `L`, placing checkpoints by the
+/// Griewank-Walther binomial split for a user-chosen number of slots `c`.
+///
+/// `new` takes `r` as the smallest value with `beta(c, r) >= L`, and keeps
+/// the snapshots at the positions that the greedy binomial split (`advance`
+/// above) picks along the tail of the range, plus the first and the last
+/// step; the other snapshots are dropped.  To reverse `[capo, fine)`, the
+/// next checkpoint `chk` is placed by the same split, the tail `[chk, fine)`
+/// is reversed with one fewer recomputation and the forward sub-range
+/// `[capo, chk)` with one fewer slot.  When no stored snapshot lies at or
+/// above `chk`, every snapshot from the nearest earlier one up to `chk` is
+/// recomputed with `restore` and kept until its own step has been reversed,
+/// so no step is recomputed more than once.  The number of live snapshots is
+/// not bounded by `c`: it can reach the number of stored checkpoints plus
+/// the length of the largest such gap, and with `c = 1` every snapshot is
+/// stored.
+pub struct RevolveChain<S, J, R> {
+    len: usize,
+    slots: usize,
+    snapshots: Vec<(usize, S)>,
+    adjoint: J,
+    restore: R,
+}
+
+impl<S, J, R> RevolveChain<S, J, R> {
+    pub fn new<I>(snapshots: I, adjoint: J, restore: R, slots: usize) -> Self
+        where I: ExactSizeIterator<Item=S> {
+        assert!(slots > 0);
+        let len = snapshots.len();
+        let r = min_recomputations(slots, len);
+        let mut keep = initial_shots(0, len, slots, r);
+        if len > 0 {
+            // `initial_shots` only ever returns the *future* checkpoints
+            // computed by `advance`; it never lands on the range's own
+            // start.  Without a checkpoint at 0, reversing the leading
+            // sub-range has nothing to restore from, so pin both ends of
+            // the chain explicitly.
+            keep.push(0);
+            keep.push(len - 1);
+            keep.sort();
+            keep.dedup();
+        }
+        let mut keep = keep.into_iter().peekable();
+        let mut stored = Vec::new();
+        for (i, s) in snapshots.enumerate() {
+            if keep.peek() == Some(&i) {
+                keep.next();
+                stored.push((i, s));
+            }
+        }
+        RevolveChain {
+            len: len,
+            slots: slots,
+            snapshots: stored,
+            adjoint: adjoint,
+            restore: restore,
+        }
+    }
+
+    pub fn sweep<G>(&self, x: G) -> G
+        where J: Fn(&S, G) -> G, R: Fn(&S) -> S, S: Clone {
+        let r = min_recomputations(self.slots, self.len);
+        let mut ctx = ReverseCtx {
+            stack: self.snapshots.clone(),
+            adjoint: &self.adjoint,
+            restore: &self.restore,
+        };
+        reverse(0, self.len, self.slots, r, x, &mut ctx)
+    }
+
+    pub fn sweep_once<G>(mut self, x: G) -> G
+        where J: Fn(&S, G) -> G, R: Fn(&S) -> S {
+        let mut stack = Vec::new();
+        ::std::mem::swap(&mut stack, &mut self.snapshots);
+        let r = min_recomputations(self.slots, self.len);
+        let mut ctx = ReverseCtx {
+            stack: stack,
+            adjoint: &self.adjoint,
+            restore: &self.restore,
+        };
+        reverse(0, self.len, self.slots, r, x, &mut ctx)
+    }
+}
+
+/// The checkpoints taken during the initial monotonic forward descent over
+/// `[capo, fine)`, before any reversal has happened.
+fn initial_shots(capo: usize, fine: usize, c: usize, r: usize) -> Vec<usize> {
+    if fine <= capo + 1 {
+        return Vec::new();
+    }
+    let chk = capo + advance(c, r, fine - capo);
+    let mut shots = vec![chk];
+    shots.extend(initial_shots(chk, fine, c, r.saturating_sub(1)));
+    shots
+}
+
+/// Bundles the pieces of `reverse`'s state that are threaded through
+/// unchanged on every recursive call, so that `reverse` itself stays under
+/// clippy's argument-count limit.
+struct ReverseCtx<'a, S, J, R> {
+    stack: Vec<(usize, S)>,
+    adjoint: &'a J,
+    restore: &'a R,
+}
+
+/// Reverses `[capo, fine)`, restoring and recursing as needed.  Every
+/// snapshot recomputed on the way stays on `ctx.stack` until its own step is
+/// reversed, so `slots` only steers where `advance` places checkpoints; it
+/// does not bound the size of `ctx.stack`.
+fn reverse<S, G, J, R>(capo: usize, fine: usize, slots: usize, r: usize,
+                        x: G, ctx: &mut ReverseCtx<S, J, R>) -> G
+    where J: Fn(&S, G) -> G, R: Fn(&S) -> S {
+    if fine <= capo {
+        return x;
+    }
+    if fine - capo == 1 {
+        // YOUTURN: apply the adjoint for this single step.
+        let (i, s) = ctx.stack.pop().expect("RevolveChain: missing checkpoint");
+        debug_assert_eq!(i, capo);
+        return (ctx.adjoint)(&s, x);
+    }
+    let chk = capo + advance(slots, r, fine - capo);
+    while ctx.stack.last().is_none_or(|&(i, _)| i < chk) {
+        // Restore from the nearest earlier checkpoint, keeping every
+        // snapshot recomputed on the way up to and including `chk`.
+        let (mut i, mut s) = ctx.stack.pop().expect("RevolveChain: nothing to restore from");
+        while i < chk {
+            let next = (ctx.restore)(&s);
+            ctx.stack.push((i, s));
+            i += 1;
+            s = next;
+        }
+        ctx.stack.push((i, s));
+    }
+    let x = reverse(chk, fine, slots, r.saturating_sub(1), x, ctx);
+    reverse(capo, chk, slots.saturating_sub(1), r, x, ctx)
+}
+
 /// Wrap a `next` function into an `Iterator`.
 pub struct Generator<F>(pub F);
 
@@ -374,4 +554,32 @@ mod tests {
         }.sweep_once(vec![1.0]);
         assert!((g[0] - expected).abs() < 1e-10);
     }
+
+    #[test]
+    fn revolve_chain() {
+        let x0 = vec![X0];
+        let expected = E.powi(N) * X0.powf(E.powi(N) - 1.0);
+
+        // Each snapshot must be the state *before* its step (matching
+        // `restore`'s contract that `restore(s1) == s2` when `s2` follows
+        // `s1`), the same convention `full_chain`'s generator uses above.
+        let mut xs = Vec::new();
+        let mut x = x0.clone();
+        for _ in 0 .. N {
+            xs.push(x.clone());
+            x = f(x);
+        }
+
+        let g = RevolveChain::new(xs.clone().into_iter(), |x: &Vec<f64>, mut g: Vec<f64>| {
+            g[0] *= E * x[0].powf(E - 1.0);
+            g
+        }, |x: &Vec<f64>| f(x.clone()), 4).sweep(vec![1.0]);
+        assert!((g[0] - expected).abs() < 1e-10);
+
+        let g = RevolveChain::new(xs.into_iter(), |x: &Vec<f64>, mut g: Vec<f64>| {
+            g[0] *= E * x[0].powf(E - 1.0);
+            g
+        }, |x: &Vec<f64>| f(x.clone()), 4).sweep_once(vec![1.0]);
+        assert!((g[0] - expected).abs() < 1e-10);
+    }
 }
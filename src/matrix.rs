@@ -0,0 +1,326 @@
+//! Differentiable dense matrices.
+//!
+//! Unlike [`tape::Tape`](../tape/struct.Tape.html), which records one tape
+//! entry per scalar operation, `MatTape` records one entry per *matrix*
+//! operation (`matmul`, elementwise add/multiply, transpose).  This keeps a
+//! single `m`x`k` times `k`x`n` matrix multiply as a single node instead of
+//! unrolling it into `m`*`n`*`k` scalar nodes, which is what makes the tape
+//! usable for anything beyond toy-sized linear algebra.
+
+use std::cell::RefCell;
+use tape::Scalar;
+
+/// A dense matrix backed by flat row-major storage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix<T>(Vec<T>, usize);
+
+impl<T> Matrix<T> {
+    /// Builds a matrix from row-major `data` with `ncols` columns.
+    pub fn new(data: Vec<T>, ncols: usize) -> Self {
+        assert_eq!(data.len() % ncols, 0);
+        Matrix(data, ncols)
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.0.len() / self.1
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.1
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.nrows(), self.ncols())
+    }
+}
+
+impl<T: Scalar> Matrix<T> {
+    pub fn zeros(shape: (usize, usize)) -> Self {
+        Matrix(vec![T::zero(); shape.0 * shape.1], shape.1)
+    }
+
+    pub fn ones(shape: (usize, usize)) -> Self {
+        Matrix(vec![T::one(); shape.0 * shape.1], shape.1)
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        assert_eq!(self.shape(), other.shape());
+        Matrix(
+            self.0.iter().zip(&other.0).map(|(a, b)| a.clone() + b.clone()).collect(),
+            self.1,
+        )
+    }
+
+    pub fn hadamard(&self, other: &Self) -> Self {
+        assert_eq!(self.shape(), other.shape());
+        Matrix(
+            self.0.iter().zip(&other.0).map(|(a, b)| a.clone() * b.clone()).collect(),
+            self.1,
+        )
+    }
+
+    pub fn transpose(&self) -> Self {
+        let (nrows, ncols) = self.shape();
+        let mut data = Vec::with_capacity(self.0.len());
+        for j in 0 .. ncols {
+            for i in 0 .. nrows {
+                data.push(self[(i, j)].clone());
+            }
+        }
+        Matrix(data, nrows)
+    }
+
+    pub fn matmul(&self, other: &Self) -> Self {
+        let (m, k) = self.shape();
+        let (k2, n) = other.shape();
+        assert_eq!(k, k2);
+        let mut data = vec![T::zero(); m * n];
+        for i in 0 .. m {
+            for p in 0 .. k {
+                let a = self[(i, p)].clone();
+                for j in 0 .. n {
+                    data[i * n + j] = data[i * n + j].clone() + a.clone() * other[(p, j)].clone();
+                }
+            }
+        }
+        Matrix(data, n)
+    }
+}
+
+impl<T> ::std::ops::Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        &self.0[i * self.1 + j]
+    }
+}
+
+impl<T> ::std::ops::IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        &mut self.0[i * self.1 + j]
+    }
+}
+
+/// The recorded operation that produced a `MatNode`, together with whatever
+/// values the reverse sweep needs in order to compute the adjoint (e.g. the
+/// two operands of a `matmul`, since its transpose-Jacobian rule depends on
+/// their values).
+enum MatOp<T> {
+    Add { dep0: usize, dep1: usize },
+    Hadamard { dep0: usize, dep1: usize, a: Matrix<T>, b: Matrix<T> },
+    MatMul { dep0: usize, dep1: usize, a: Matrix<T>, b: Matrix<T> },
+    Transpose { dep0: usize },
+}
+
+struct MatNode<T> {
+    shape: (usize, usize),
+    op: Option<MatOp<T>>,
+}
+
+pub struct MatTape<T> { nodes: RefCell<Vec<MatNode<T>>> }
+
+impl<T: Scalar> MatTape<T> {
+    pub fn new() -> Self {
+        MatTape { nodes: RefCell::new(Vec::new()) }
+    }
+
+    pub fn var<'t>(&'t self, value: Matrix<T>) -> MatVar<'t, T> {
+        let shape = value.shape();
+        MatVar {
+            tape: self,
+            index: self.push(shape, None),
+            value: value,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.borrow().len()
+    }
+
+    fn push(&self, shape: (usize, usize), op: Option<MatOp<T>>) -> usize {
+        let mut nodes = self.nodes.borrow_mut();
+        let len = nodes.len();
+        nodes.push(MatNode { shape: shape, op: op });
+        len
+    }
+}
+
+#[derive(Clone)]
+pub struct MatVar<'t, T: 't> {
+    tape: &'t MatTape<T>,
+    index: usize,
+    value: Matrix<T>,
+}
+
+impl<'t, T: Scalar> MatVar<'t, T> {
+    pub fn value(&self) -> &Matrix<T> {
+        &self.value
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.value.shape()
+    }
+
+    /// Reverse-sweeps the tape, seeding this var's adjoint with all ones
+    /// (i.e. computing the gradient of the sum of its entries).
+    pub fn grad(&self) -> MatGrad<T> {
+        let len = self.tape.len();
+        let nodes = self.tape.nodes.borrow();
+        let mut derivs: Vec<Matrix<T>> =
+            nodes.iter().map(|node| Matrix::zeros(node.shape)).collect();
+        derivs[self.index] = Matrix::ones(self.value.shape());
+        for i in (0 .. len).rev() {
+            let deriv = derivs[i].clone();
+            match nodes[i].op {
+                None => {}
+                Some(MatOp::Add { dep0, dep1 }) => {
+                    derivs[dep0] = derivs[dep0].add(&deriv);
+                    derivs[dep1] = derivs[dep1].add(&deriv);
+                }
+                Some(MatOp::Hadamard { dep0, dep1, ref a, ref b }) => {
+                    derivs[dep0] = derivs[dep0].add(&deriv.hadamard(b));
+                    derivs[dep1] = derivs[dep1].add(&deriv.hadamard(a));
+                }
+                Some(MatOp::MatMul { dep0, dep1, ref a, ref b }) => {
+                    derivs[dep0] = derivs[dep0].add(&deriv.matmul(&b.transpose()));
+                    derivs[dep1] = derivs[dep1].add(&a.transpose().matmul(&deriv));
+                }
+                Some(MatOp::Transpose { dep0 }) => {
+                    derivs[dep0] = derivs[dep0].add(&deriv.transpose());
+                }
+            }
+        }
+        MatGrad { derivs: derivs }
+    }
+
+    pub fn transpose(self) -> Self {
+        let value = self.value.transpose();
+        let shape = value.shape();
+        MatVar {
+            tape: self.tape,
+            index: self.tape.push(shape, Some(MatOp::Transpose { dep0: self.index })),
+            value: value,
+        }
+    }
+}
+
+impl<'t, T: Scalar> ::std::ops::Add for MatVar<'t, T> {
+    type Output = MatVar<'t, T>;
+    fn add(self, other: MatVar<'t, T>) -> Self::Output {
+        assert_eq!(self.tape as *const MatTape<T>, other.tape as *const MatTape<T>);
+        let value = self.value.add(&other.value);
+        let shape = value.shape();
+        MatVar {
+            tape: self.tape,
+            index: self.tape.push(shape, Some(MatOp::Add {
+                dep0: self.index,
+                dep1: other.index,
+            })),
+            value: value,
+        }
+    }
+}
+
+impl<'t, T: Scalar> MatVar<'t, T> {
+    /// Elementwise (Hadamard) product.
+    pub fn hadamard(self, other: Self) -> Self {
+        assert_eq!(self.tape as *const MatTape<T>, other.tape as *const MatTape<T>);
+        let value = self.value.hadamard(&other.value);
+        let shape = value.shape();
+        MatVar {
+            tape: self.tape,
+            index: self.tape.push(shape, Some(MatOp::Hadamard {
+                dep0: self.index,
+                dep1: other.index,
+                a: self.value.clone(),
+                b: other.value.clone(),
+            })),
+            value: value,
+        }
+    }
+
+    /// Matrix product `self` * `other`.
+    pub fn matmul(self, other: Self) -> Self {
+        assert_eq!(self.tape as *const MatTape<T>, other.tape as *const MatTape<T>);
+        let value = self.value.matmul(&other.value);
+        let shape = value.shape();
+        MatVar {
+            tape: self.tape,
+            index: self.tape.push(shape, Some(MatOp::MatMul {
+                dep0: self.index,
+                dep1: other.index,
+                a: self.value.clone(),
+                b: other.value.clone(),
+            })),
+            value: value,
+        }
+    }
+}
+
+pub struct MatGrad<T> { derivs: Vec<Matrix<T>> }
+
+impl<T: Scalar> MatGrad<T> {
+    pub fn wrt<'t>(&self, var: &MatVar<'t, T>) -> &Matrix<T> {
+        &self.derivs[var.index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Matrix, MatTape};
+
+    #[test]
+    fn matmul_adjoint() {
+        let t: MatTape<f64> = MatTape::new();
+        let a = t.var(Matrix::new(vec![1.0, 2.0, 3.0, 4.0], 2));
+        let b = t.var(Matrix::new(vec![5.0, 6.0, 7.0, 8.0], 2));
+        let c = a.clone().matmul(b.clone());
+        assert_eq!(c.value(), &Matrix::new(vec![19.0, 22.0, 43.0, 50.0], 2));
+
+        let grad = c.grad();
+        // d(sum C)/dA = ones(2,2) * B^T, d(sum C)/dB = A^T * ones(2,2)
+        let g = Matrix::ones((2, 2));
+        assert_eq!(grad.wrt(&a), &g.matmul(&b.value().transpose()));
+        assert_eq!(grad.wrt(&b), &a.value().transpose().matmul(&g));
+    }
+
+    #[test]
+    fn add_adjoint() {
+        let t: MatTape<f64> = MatTape::new();
+        let a = t.var(Matrix::new(vec![1.0, 2.0, 3.0, 4.0], 2));
+        let b = t.var(Matrix::new(vec![5.0, 6.0, 7.0, 8.0], 2));
+        let c = a.clone() + b.clone();
+        assert_eq!(c.value(), &Matrix::new(vec![6.0, 8.0, 10.0, 12.0], 2));
+
+        let grad = c.grad();
+        // d(sum A+B)/dA = d(sum A+B)/dB = ones(2,2)
+        let ones = Matrix::ones((2, 2));
+        assert_eq!(grad.wrt(&a), &ones);
+        assert_eq!(grad.wrt(&b), &ones);
+    }
+
+    #[test]
+    fn hadamard_adjoint() {
+        let t: MatTape<f64> = MatTape::new();
+        let a = t.var(Matrix::new(vec![1.0, 2.0, 3.0, 4.0], 2));
+        let b = t.var(Matrix::new(vec![5.0, 6.0, 7.0, 8.0], 2));
+        let c = a.clone().hadamard(b.clone());
+        assert_eq!(c.value(), &Matrix::new(vec![5.0, 12.0, 21.0, 32.0], 2));
+
+        let grad = c.grad();
+        // d(sum A⊙B)/dA = B, d(sum A⊙B)/dB = A
+        assert_eq!(grad.wrt(&a), b.value());
+        assert_eq!(grad.wrt(&b), a.value());
+    }
+
+    #[test]
+    fn transpose_adjoint() {
+        let t: MatTape<f64> = MatTape::new();
+        let a = t.var(Matrix::new(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3));
+        let c = a.clone().transpose();
+        assert_eq!(c.value(), &Matrix::new(vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0], 2));
+
+        let grad = c.grad();
+        // d(sum A^T)/dA = ones in A's own (untransposed) shape
+        assert_eq!(grad.wrt(&a), &Matrix::ones((2, 3)));
+    }
+}
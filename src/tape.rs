@@ -1,19 +1,81 @@
 use std::cell::RefCell;
 
-#[derive(Clone, Copy)]
-struct Node {
-    weights: [f64; 2],
+/// The numeric interface required by the tape's push/sweep machinery and by
+/// the arithmetic operators (`+`, `-`, `*`, negation) on `Var`.
+pub trait Scalar: Clone
+    + ::std::ops::Add<Output=Self>
+    + ::std::ops::Sub<Output=Self>
+    + ::std::ops::Neg<Output=Self>
+    + ::std::ops::Mul<Output=Self> {
+    fn zero() -> Self;
+    fn one() -> Self;
+}
+
+impl Scalar for f64 {
+    fn zero() -> Self { 0.0 }
+    fn one() -> Self { 1.0 }
+}
+
+impl Scalar for f32 {
+    fn zero() -> Self { 0.0 }
+    fn one() -> Self { 1.0 }
+}
+
+/// Extends `Scalar` with division and the transcendental functions needed by
+/// intrinsics such as `sin` and `exp`.  Kept separate from `Scalar` so that
+/// ops which don't need them (e.g. `Add`, `Mul`) can be used with scalar
+/// types -- such as modular integers or exact rationals -- that have no
+/// sensible `sqrt` or `ln`.
+pub trait Float: Scalar + ::std::ops::Div<Output=Self> {
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn powf(self, p: Self) -> Self;
+    fn recip(self) -> Self;
+    fn tanh(self) -> Self;
+}
+
+impl Float for f64 {
+    fn sin(self) -> Self { f64::sin(self) }
+    fn cos(self) -> Self { f64::cos(self) }
+    fn tan(self) -> Self { f64::tan(self) }
+    fn exp(self) -> Self { f64::exp(self) }
+    fn ln(self) -> Self { f64::ln(self) }
+    fn sqrt(self) -> Self { f64::sqrt(self) }
+    fn powf(self, p: Self) -> Self { f64::powf(self, p) }
+    fn recip(self) -> Self { f64::recip(self) }
+    fn tanh(self) -> Self { f64::tanh(self) }
+}
+
+impl Float for f32 {
+    fn sin(self) -> Self { f32::sin(self) }
+    fn cos(self) -> Self { f32::cos(self) }
+    fn tan(self) -> Self { f32::tan(self) }
+    fn exp(self) -> Self { f32::exp(self) }
+    fn ln(self) -> Self { f32::ln(self) }
+    fn sqrt(self) -> Self { f32::sqrt(self) }
+    fn powf(self, p: Self) -> Self { f32::powf(self, p) }
+    fn recip(self) -> Self { f32::recip(self) }
+    fn tanh(self) -> Self { f32::tanh(self) }
+}
+
+#[derive(Clone)]
+struct Node<T> {
+    weights: [T; 2],
     deps: [usize; 2],
 }
 
-pub struct Tape { nodes: RefCell<Vec<Node>> }
+pub struct Tape<T> { nodes: RefCell<Vec<Node<T>>> }
 
-impl Tape {
+impl<T: Scalar> Tape<T> {
     pub fn new() -> Self {
         Tape { nodes: RefCell::new(Vec::new()) }
     }
 
-    pub fn var<'t>(&'t self, value: f64) -> Var<'t> {
+    pub fn var<'t>(&'t self, value: T) -> Var<'t, T> {
         Var {
             tape: self,
             value: value,
@@ -21,33 +83,29 @@ impl Tape {
         }
     }
 
-    fn len(&self) -> usize {
-        self.nodes.borrow().len()
-    }
-
     fn push0(&self) -> usize {
         let mut nodes = self.nodes.borrow_mut();
         let len = nodes.len();
         nodes.push(Node {
-            weights: [0.0, 0.0],
+            weights: [T::zero(), T::zero()],
             deps: [len, len],
         });
         len
     }
 
-    fn push1(&self, dep0: usize, weight0: f64) -> usize {
+    fn push1(&self, dep0: usize, weight0: T) -> usize {
         let mut nodes = self.nodes.borrow_mut();
         let len = nodes.len();
         nodes.push(Node {
-            weights: [weight0, 0.0],
+            weights: [weight0, T::zero()],
             deps: [dep0, len],
         });
         len
     }
 
     fn push2(&self,
-             dep0: usize, weight0: f64,
-             dep1: usize, weight1: f64) -> usize {
+             dep0: usize, weight0: T,
+             dep1: usize, weight1: T) -> usize {
         let mut nodes = self.nodes.borrow_mut();
         let len = nodes.len();
         nodes.push(Node {
@@ -56,81 +114,282 @@ impl Tape {
         });
         len
     }
+
+    /// Reverse-sweeps the tape from an arbitrary set of seeded cotangents,
+    /// rather than a single `1.0` seed at one output.
+    pub fn grad_seeded<'t>(&'t self, seeds: &[(Var<'t, T>, T)]) -> Grad<T> {
+        let mut grad = Grad { derivs: Vec::new() };
+        self.grad_seeded_into(seeds, &mut grad);
+        grad
+    }
+
+    /// Like `grad_seeded`, but writes into an existing `Grad` buffer instead
+    /// of allocating a new one.
+    pub fn grad_seeded_into<'t>(&'t self, seeds: &[(Var<'t, T>, T)], grad: &mut Grad<T>) {
+        let nodes = self.nodes.borrow();
+        grad.derivs.clear();
+        grad.derivs.resize(nodes.len(), T::zero());
+        for &(ref var, ref seed) in seeds {
+            assert_eq!(self as *const Tape<T>, var.tape as *const Tape<T>);
+            grad.derivs[var.index] = grad.derivs[var.index].clone() + seed.clone();
+        }
+        sweep(&nodes, &mut grad.derivs);
+    }
+}
+
+/// Runs one reverse sweep over `nodes`, accumulating weighted cotangents
+/// from each node into its dependencies' slots in `derivs`.
+fn sweep<T: Scalar>(nodes: &[Node<T>], derivs: &mut [T]) {
+    for i in (0 .. nodes.len()).rev() {
+        let node = &nodes[i];
+        let deriv = derivs[i].clone();
+        for j in 0 .. 2 {
+            let dep = node.deps[j];
+            derivs[dep] = derivs[dep].clone() + node.weights[j].clone() * deriv.clone();
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
-pub struct Var<'t> {
-    tape: &'t Tape,
+pub struct Var<'t, T: 't> {
+    tape: &'t Tape<T>,
     index: usize,
-    value: f64,
+    value: T,
 }
 
-impl<'t> Var<'t> {
-    pub fn value(&self) -> f64 {
-        self.value
+impl<'t, T: Clone> Clone for Var<'t, T> {
+    fn clone(&self) -> Self {
+        Var { tape: self.tape, index: self.index, value: self.value.clone() }
     }
+}
 
-    pub fn grad(&self) -> Grad {
-        let len = self.tape.len();
-        let nodes = self.tape.nodes.borrow();
-        let mut derivs = vec![0.0; len];
-        derivs[self.index] = 1.0;
-        for i in (0 .. len).rev() {
-            let node = nodes[i];
-            let deriv = derivs[i];
-            for j in 0 .. 2 {
-                derivs[node.deps[j]] += node.weights[j] * deriv;
-            }
-        }
-        Grad { derivs: derivs }
+impl<'t, T: Copy> Copy for Var<'t, T> {}
+
+impl<'t, T: Scalar> Var<'t, T> {
+    pub fn value(&self) -> T {
+        self.value.clone()
     }
 
+    pub fn grad(&self) -> Grad<T> {
+        self.tape.grad_seeded(&[(self.clone(), T::one())])
+    }
+
+    /// Like `grad`, but reuses `grad`'s existing buffer instead of
+    /// allocating a fresh one.  See `Tape::grad_seeded_into`.
+    pub fn grad_into(&self, grad: &mut Grad<T>) {
+        self.tape.grad_seeded_into(&[(self.clone(), T::one())], grad)
+    }
+}
+
+impl<'t, T: Float> Var<'t, T> {
     pub fn sin(self) -> Self {
         Var {
             tape: self.tape,
-            value: self.value.sin(),
+            value: self.value.clone().sin(),
             index: self.tape.push1(
                 self.index, self.value.cos(),
             ),
         }
     }
+
+    pub fn cos(self) -> Self {
+        Var {
+            tape: self.tape,
+            value: self.value.clone().cos(),
+            index: self.tape.push1(
+                self.index, -self.value.sin(),
+            ),
+        }
+    }
+
+    pub fn tan(self) -> Self {
+        let c = self.value.clone().cos();
+        Var {
+            tape: self.tape,
+            value: self.value.tan(),
+            index: self.tape.push1(
+                self.index, T::one() / (c.clone() * c),
+            ),
+        }
+    }
+
+    pub fn exp(self) -> Self {
+        let value = self.value.exp();
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(self.index, value.clone()),
+            value: value,
+        }
+    }
+
+    pub fn ln(self) -> Self {
+        Var {
+            tape: self.tape,
+            value: self.value.clone().ln(),
+            index: self.tape.push1(self.index, self.value.recip()),
+        }
+    }
+
+    pub fn sqrt(self) -> Self {
+        let value = self.value.sqrt();
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(self.index, T::one() / (value.clone() + value.clone())),
+            value: value,
+        }
+    }
+
+    pub fn powf(self, p: T) -> Self {
+        let value = self.value.clone().powf(p.clone());
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(
+                self.index, p.clone() * self.value.powf(p - T::one()),
+            ),
+            value: value,
+        }
+    }
+
+    pub fn recip(self) -> Self {
+        let value = self.value.recip();
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(self.index, -(value.clone() * value.clone())),
+            value: value,
+        }
+    }
+
+    pub fn tanh(self) -> Self {
+        let value = self.value.tanh();
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(self.index, T::one() - value.clone() * value.clone()),
+            value: value,
+        }
+    }
 }
 
-impl<'t> ::std::ops::Add for Var<'t> {
-    type Output = Var<'t>;
-    fn add(self, other: Var<'t>) -> Self::Output {
-        assert_eq!(self.tape as *const Tape, other.tape as *const Tape);
+impl<'t, T: Scalar> ::std::ops::Add for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn add(self, other: Var<'t, T>) -> Self::Output {
+        assert_eq!(self.tape as *const Tape<T>, other.tape as *const Tape<T>);
         Var {
             tape: self.tape,
             value: self.value + other.value,
             index: self.tape.push2(
-                self.index, 1.0,
-                other.index, 1.0,
+                self.index, T::one(),
+                other.index, T::one(),
            ),
         }
     }
 }
 
-impl<'t> ::std::ops::Mul for Var<'t> {
-    type Output = Var<'t>;
-    fn mul(self, other: Var<'t>) -> Self::Output {
-        assert_eq!(self.tape as *const Tape, other.tape as *const Tape);
+impl<'t, T: Scalar> ::std::ops::Mul for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn mul(self, other: Var<'t, T>) -> Self::Output {
+        assert_eq!(self.tape as *const Tape<T>, other.tape as *const Tape<T>);
         Var {
             tape: self.tape,
+            index: self.tape.push2(
+                self.index, other.value.clone(),
+                other.index, self.value.clone(),
+            ),
             value: self.value * other.value,
+        }
+    }
+}
+
+impl<'t, T: Scalar> ::std::ops::Sub for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn sub(self, other: Var<'t, T>) -> Self::Output {
+        assert_eq!(self.tape as *const Tape<T>, other.tape as *const Tape<T>);
+        Var {
+            tape: self.tape,
+            value: self.value - other.value,
             index: self.tape.push2(
-                self.index, other.value,
-                other.index, self.value,
+                self.index, T::one(),
+                other.index, -T::one(),
+           ),
+        }
+    }
+}
+
+impl<'t, T: Scalar> ::std::ops::Neg for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn neg(self) -> Self::Output {
+        Var {
+            tape: self.tape,
+            value: -self.value,
+            index: self.tape.push1(self.index, -T::one()),
+        }
+    }
+}
+
+impl<'t, T: Float> ::std::ops::Div for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn div(self, other: Var<'t, T>) -> Self::Output {
+        assert_eq!(self.tape as *const Tape<T>, other.tape as *const Tape<T>);
+        let inv_other = other.value.recip();
+        Var {
+            tape: self.tape,
+            index: self.tape.push2(
+                self.index, inv_other.clone(),
+                other.index, -(self.value.clone() * inv_other.clone() * inv_other.clone()),
             ),
+            value: self.value * inv_other,
         }
     }
 }
 
-pub struct Grad { derivs: Vec<f64> }
+impl<'t, T: Scalar> ::std::ops::Add<T> for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn add(self, other: T) -> Self::Output {
+        Var {
+            tape: self.tape,
+            value: self.value + other,
+            index: self.tape.push1(self.index, T::one()),
+        }
+    }
+}
 
-impl Grad {
-    pub fn wrt<'t>(&self, var: Var<'t>) -> f64 {
-        self.derivs[var.index]
+impl<'t, T: Scalar> ::std::ops::Sub<T> for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn sub(self, other: T) -> Self::Output {
+        Var {
+            tape: self.tape,
+            value: self.value - other,
+            index: self.tape.push1(self.index, T::one()),
+        }
+    }
+}
+
+impl<'t, T: Scalar> ::std::ops::Mul<T> for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn mul(self, other: T) -> Self::Output {
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(self.index, other.clone()),
+            value: self.value * other,
+        }
+    }
+}
+
+impl<'t, T: Float> ::std::ops::Div<T> for Var<'t, T> {
+    type Output = Var<'t, T>;
+    fn div(self, other: T) -> Self::Output {
+        let inv_other = other.recip();
+        Var {
+            tape: self.tape,
+            index: self.tape.push1(self.index, inv_other.clone()),
+            value: self.value * inv_other,
+        }
+    }
+}
+
+pub struct Grad<T> { derivs: Vec<T> }
+
+impl<T: Scalar> Grad<T> {
+    pub fn wrt<'t>(&self, var: Var<'t, T>) -> T {
+        self.derivs[var.index].clone()
     }
 }
 
@@ -140,7 +399,7 @@ mod tests {
 
     #[test]
     fn x_times_y_plus_sin_x() {
-        let t = Tape::new();
+        let t: Tape<f64> = Tape::new();
         let x = t.var(0.5);
         let y = t.var(4.2);
         let z = x * y + x.sin();
@@ -149,4 +408,114 @@ mod tests {
         assert!((grad.wrt(x) - (y.value + x.value.cos())).abs() <= 1e-15);
         assert!((grad.wrt(y) - x.value).abs() <= 1e-15);
     }
+
+    #[test]
+    fn sub_and_neg() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let y = t.var(4.2);
+        let z = -(x - y);
+        let grad = z.grad();
+        assert!((z.value() - (y.value() - x.value())).abs() <= 1e-15);
+        assert!((grad.wrt(x) - -1.0).abs() <= 1e-15);
+        assert!((grad.wrt(y) - 1.0).abs() <= 1e-15);
+    }
+
+    #[test]
+    fn div() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let y = t.var(4.2);
+        let z = x / y;
+        let grad = z.grad();
+        assert!((z.value() - x.value() / y.value()).abs() <= 1e-15);
+        assert!((grad.wrt(x) - 1.0 / y.value()).abs() <= 1e-15);
+        assert!((grad.wrt(y) - -x.value() / (y.value() * y.value())).abs() <= 1e-12);
+    }
+
+    #[test]
+    fn scalar_constants() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let z = (x + 1.0) * 2.0 - 0.5;
+        let grad = z.grad();
+        assert!((z.value() - ((x.value() + 1.0) * 2.0 - 0.5)).abs() <= 1e-15);
+        assert!((grad.wrt(x) - 2.0).abs() <= 1e-15);
+    }
+
+    #[test]
+    fn exp_and_ln() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let z = x.exp().ln();
+        let grad = z.grad();
+        assert!((z.value() - x.value()).abs() <= 1e-12);
+        assert!((grad.wrt(x) - 1.0).abs() <= 1e-12);
+    }
+
+    #[test]
+    fn sqrt() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(2.0);
+        let z = x.sqrt();
+        let grad = z.grad();
+        assert!((z.value() - x.value().sqrt()).abs() <= 1e-15);
+        assert!((grad.wrt(x) - 1.0 / (2.0 * x.value().sqrt())).abs() <= 1e-12);
+    }
+
+    #[test]
+    fn powf() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(2.0);
+        let z = x.powf(3.0);
+        let grad = z.grad();
+        assert!((z.value() - 8.0).abs() <= 1e-12);
+        assert!((grad.wrt(x) - 3.0 * x.value().powf(2.0)).abs() <= 1e-12);
+    }
+
+    #[test]
+    fn cos_tan_tanh_recip() {
+        let h = 1e-6;
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let z = x.cos() + x.tan() + x.tanh() + x.recip();
+        let grad = z.grad();
+
+        let f = |v: f64| v.cos() + v.tan() + v.tanh() + v.recip();
+        let numeric = (f(x.value() + h) - f(x.value() - h)) / (2.0 * h);
+        assert!((grad.wrt(x) - numeric).abs() <= 1e-6);
+    }
+
+    #[test]
+    fn grad_seeded_vjp() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let y = t.var(4.2);
+        let u = x * y;
+        let v = x + y;
+
+        // vector-Jacobian product with seed [2.0, 3.0] for outputs [u, v]
+        // is equivalent to seeding the single combined objective 2*u + 3*v.
+        let grad = t.grad_seeded(&[(u, 2.0), (v, 3.0)]);
+        let combined = (u * t.var(2.0) + v * t.var(3.0)).grad();
+        assert!((grad.wrt(x) - combined.wrt(x)).abs() <= 1e-12);
+        assert!((grad.wrt(y) - combined.wrt(y)).abs() <= 1e-12);
+    }
+
+    #[test]
+    fn grad_into_reuses_buffer() {
+        let t: Tape<f64> = Tape::new();
+        let x = t.var(0.5);
+        let y = t.var(4.2);
+        let z = x * y + x.sin();
+
+        let mut grad = z.grad();
+        let first = grad.wrt(x);
+
+        let w = t.var(1.5);
+        let z2 = z * w;
+        z2.grad_into(&mut grad);
+        assert!((grad.wrt(x) - (first * w.value())).abs() <= 1e-12);
+        assert!((grad.wrt(w) - z.value()).abs() <= 1e-12);
+    }
 }